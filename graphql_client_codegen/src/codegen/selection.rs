@@ -8,8 +8,9 @@ use crate::resolution::SelectionRef;
 use crate::schema::TypeRef;
 use crate::shared::field_rename_annotation;
 use crate::{
+    deprecation::DeprecationStrategy,
     field_type::GraphqlTypeQualifier,
-    // deprecation::DeprecationStrategy,
+    rename::RenameRule,
     resolution::{OperationRef, ResolvedQuery, Selection, SelectionId},
     schema::{Schema, TypeId},
     shared::keyword_replace,
@@ -20,6 +21,37 @@ use proc_macro2::{Ident, Span, TokenStream};
 use quote::quote;
 use std::borrow::Cow;
 
+/// Transform a GraphQL name into a Rust identifier according to the configured rename rule.
+fn apply_rename_rule(name: &str, rule: RenameRule) -> String {
+    match rule {
+        RenameRule::CamelCase => name.to_lower_camel_case(),
+        RenameRule::SnakeCase => name.to_snake_case(),
+        RenameRule::ScreamingSnakeCase => name.to_shouty_snake_case(),
+        RenameRule::PascalCase => name.to_upper_camel_case(),
+        RenameRule::None => name.to_string(),
+    }
+}
+
+/// If `selection_set` consists of exactly one `FragmentSpread` targeting `type_id`, with no
+/// sibling scalar/typename fields, return the spread fragment. The caller can then type its
+/// field directly as the fragment's generated struct instead of generating a wrapper struct
+/// that only flattens that one fragment.
+fn single_fragment_spread<'a>(
+    context: &ExpandedSelection<'a>,
+    selection_set: &[SelectionId],
+    type_id: TypeId,
+) -> Option<FragmentRef<'a>> {
+    let [only_id] = selection_set else {
+        return None;
+    };
+
+    let selection_ref = context.get_selection_ref(*only_id);
+    let fragment_id = selection_ref.selection().as_fragment_spread()?;
+    let fragment = context.get_fragment_ref(*fragment_id);
+
+    (fragment.on() == type_id).then_some(fragment)
+}
+
 pub(crate) fn render_response_data_fields<'a>(
     operation: &OperationRef<'a>,
     response_derives: &impl quote::ToTokens,
@@ -84,10 +116,9 @@ fn calculate_selection<'a>(
     struct_id: ResponseTypeId,
     type_ref: TypeRef<'a>,
 ) {
-    // TODO: if the selection has one item, we can sometimes generate fewer structs (e.g. single fragment spread)
-
-    // If we are on a union or an interface, we need to generate an enum that matches the variants _exhaustively_,
-    // including an `Other { #serde(rename = "__typename") typename: String }` variant.
+    // If we are on a union or an interface, we need to generate an enum that matches the variants exhaustively,
+    // including a unit `Other` variant tagged `#[serde(other)]`, for concrete types we have no
+    // selection for.
     {
         let variants: Option<Cow<'_, [TypeId]>> = match type_ref.type_id() {
             TypeId::Interface(interface_id) => {
@@ -107,8 +138,10 @@ fn calculate_selection<'a>(
             for variant in variants.as_ref() {
                 let schema_type = context.schema().type_ref(*variant);
                 let variant_name_str = schema_type.name();
+                let variant_rust_name =
+                    apply_rename_rule(variant_name_str, context.options().rename_rule());
 
-                let selection = selection_set
+                let inline_fragment_selection = selection_set
                     .iter()
                     .map(|id| context.get_selection_ref(*id))
                     .filter_map(|selection_ref| {
@@ -119,46 +152,101 @@ fn calculate_selection<'a>(
                     })
                     .find(|(_selection_ref, inline_fragment)| inline_fragment.type_id == *variant);
 
-                if let Some((selection_ref, inline_fragment)) = selection {
-                    let variant_struct_name_str = selection_ref.full_path_prefix();
+                // A type-refining fragment spread is a named fragment whose `on` type is the
+                // variant itself (as opposed to the interface/union we are currently expanding).
+                let type_refining_fragment_spread = inline_fragment_selection.is_none()
+                    .then(|| {
+                        selection_set
+                            .iter()
+                            .map(|id| context.get_selection_ref(*id))
+                            .filter_map(|selection_ref| {
+                                selection_ref
+                                    .selection()
+                                    .as_fragment_spread()
+                                    .map(|fragment_id| context.get_fragment_ref(*fragment_id))
+                                    .filter(|fragment| fragment.on() == *variant)
+                                    .map(|fragment| (selection_ref, fragment))
+                            })
+                            .next()
+                    })
+                    .flatten();
 
-                    todo!("There will be a struct/type for the variant if there is an inline OR type-refining fragment there.");
+                if let Some((selection_ref, _inline_fragment)) = inline_fragment_selection {
+                    let variant_struct_name_str = selection_ref.full_path_prefix();
 
                     context.push_variant(ExpandedVariant {
                         name: variant_name_str.into(),
+                        rust_name: variant_rust_name.clone().into(),
                         variant_type: Some(variant_struct_name_str.clone().into()),
+                        is_other: false,
                         on: struct_id,
                     });
 
-                    let expanded_type = ExpandedType {
+                    let variant_struct_id = context.push_type(ExpandedType {
                         name: variant_struct_name_str.into(),
                         schema_type,
-                    };
-
-                    let struct_id = context.push_type(expanded_type);
+                    });
 
                     calculate_selection(
                         context,
                         selection_ref.subselection_ids(),
-                        struct_id,
+                        variant_struct_id,
                         schema_type,
                     );
+                } else if let Some((selection_ref, fragment)) = type_refining_fragment_spread {
+                    let variant_struct_name_str = selection_ref.full_path_prefix();
+
+                    context.push_variant(ExpandedVariant {
+                        name: variant_name_str.into(),
+                        rust_name: variant_rust_name.clone().into(),
+                        variant_type: Some(variant_struct_name_str.clone().into()),
+                        is_other: false,
+                        on: struct_id,
+                    });
+
+                    let variant_struct_id = context.push_type(ExpandedType {
+                        name: variant_struct_name_str.into(),
+                        schema_type,
+                    });
+
+                    // Push the fragment down into the variant's own struct instead of the
+                    // parent, since it only applies to this one variant.
+                    context.push_field(ExpandedField {
+                        field_type: fragment.name().into(),
+                        field_type_qualifiers: &[GraphqlTypeQualifier::Required],
+                        graphql_name: fragment.name(),
+                        rust_name: keyword_replace(apply_rename_rule(
+                            fragment.name(),
+                            context.options().rename_rule(),
+                        )),
+                        struct_id: variant_struct_id,
+                        flatten: true,
+                        is_deprecated: false,
+                        deprecation_message: None,
+                    });
                 } else {
                     context.push_variant(ExpandedVariant {
                         name: variant_name_str.into(),
+                        rust_name: variant_rust_name.into(),
                         on: struct_id,
                         variant_type: None,
+                        is_other: false,
                     });
                 }
             }
 
-            // push the fragments on variants down
-
-            // meaning get all the fragment spreads on one of the variants, and add it to the type for that variant....
-            todo!("push the fragments on variants down");
-
-            // Finish by adding the Other variant
-            todo!("add the Other variant");
+            // Finish by adding the catch-all `Other` variant, so the enum can represent any
+            // concrete type the schema may add that we do not have a selection for. Serde only
+            // allows `#[serde(other)]` on a genuine unit variant of an internally tagged enum, so
+            // `Other` cannot carry the unrecognized `__typename` itself; it just has to exist for
+            // deserialization to fall back to instead of erroring out.
+            context.push_variant(ExpandedVariant {
+                name: Cow::Borrowed("Other"),
+                rust_name: Cow::Borrowed("Other"),
+                variant_type: None,
+                is_other: true,
+                on: struct_id,
+            });
         }
     }
 
@@ -171,6 +259,14 @@ fn calculate_selection<'a>(
                 let schema_field = field.schema_field(context.schema());
                 let field_type = schema_field.field_type();
 
+                let is_deprecated = schema_field.is_deprecated();
+                let deprecation_message = schema_field.deprecation_message().map(Cow::Borrowed);
+
+                if is_deprecated && context.options().deprecation_strategy() == DeprecationStrategy::Deny
+                {
+                    continue;
+                }
+
                 match field_type.type_id() {
                     TypeId::Enum(enm) => {
                         context.push_field(ExpandedField {
@@ -180,6 +276,8 @@ fn calculate_selection<'a>(
                             field_type: context.schema().r#enum(enm).name().into(),
                             field_type_qualifiers: schema_field.type_qualifiers(),
                             flatten: false,
+                            is_deprecated,
+                            deprecation_message,
                         });
                     }
                     TypeId::Scalar(scalar) => {
@@ -192,36 +290,67 @@ fn calculate_selection<'a>(
                             struct_id,
                             rust_name,
                             flatten: false,
+                            is_deprecated,
+                            deprecation_message,
                         });
                     }
                     TypeId::Object(_) | TypeId::Interface(_) | TypeId::Union(_) => {
-                        let struct_name_string = selection_ref.full_path_prefix();
-
-                        context.push_field(ExpandedField {
-                            struct_id,
-                            graphql_name,
-                            rust_name,
-                            field_type_qualifiers: schema_field.type_qualifiers(),
-                            field_type: Cow::Owned(struct_name_string.clone()),
-                            flatten: false,
-                        });
-
-                        let type_id = context.push_type(ExpandedType {
-                            name: Cow::Owned(struct_name_string),
-                            schema_type: field_type,
-                        });
-
-                        calculate_selection(
-                            context,
-                            selection_ref.subselection_ids(),
-                            type_id,
-                            field_type,
-                        );
+                        let subselection_ids = selection_ref.subselection_ids();
+
+                        // If the whole sub-selection is a single fragment spread on the field's
+                        // own type, the fragment's generated struct already has the right shape:
+                        // we can type the field directly as that struct and skip generating (and
+                        // flattening into) an extra wrapper struct.
+                        if let Some(fragment) =
+                            single_fragment_spread(context, subselection_ids, field_type.type_id())
+                        {
+                            context.push_field(ExpandedField {
+                                struct_id,
+                                graphql_name,
+                                rust_name,
+                                field_type_qualifiers: schema_field.type_qualifiers(),
+                                field_type: fragment.name().into(),
+                                flatten: false,
+                                is_deprecated,
+                                deprecation_message,
+                            });
+                        } else {
+                            let struct_name_string = selection_ref.full_path_prefix();
+
+                            context.push_field(ExpandedField {
+                                struct_id,
+                                graphql_name,
+                                rust_name,
+                                field_type_qualifiers: schema_field.type_qualifiers(),
+                                field_type: Cow::Owned(struct_name_string.clone()),
+                                flatten: false,
+                                is_deprecated,
+                                deprecation_message,
+                            });
+
+                            let type_id = context.push_type(ExpandedType {
+                                name: Cow::Owned(struct_name_string),
+                                schema_type: field_type,
+                            });
+
+                            calculate_selection(context, subselection_ids, type_id, field_type);
+                        }
                     }
                     TypeId::Input(_) => unreachable!("field selection on input type"),
                 };
             }
-            Selection::Typename => (),
+            Selection::Typename => {
+                context.push_field(ExpandedField {
+                    graphql_name: "__typename",
+                    rust_name: Cow::Borrowed("typename"),
+                    field_type: Cow::Borrowed("String"),
+                    field_type_qualifiers: &[GraphqlTypeQualifier::Required],
+                    struct_id,
+                    flatten: false,
+                    is_deprecated: false,
+                    deprecation_message: None,
+                });
+            }
             Selection::InlineFragment(_inline) => (),
             Selection::FragmentSpread(fragment_id) => {
                 // FIXME: we need to identify if the fragment is on the field itself, or on an union/interface variant of it.
@@ -234,8 +363,8 @@ fn calculate_selection<'a>(
                     continue;
                 }
 
-                let original_field_name = fragment.name().to_snake_case();
-                let final_field_name = keyword_replace(original_field_name);
+                let final_field_name =
+                    keyword_replace(apply_rename_rule(fragment.name(), context.options().rename_rule()));
 
                 context.push_field(ExpandedField {
                     field_type: fragment.name().into(),
@@ -244,6 +373,8 @@ fn calculate_selection<'a>(
                     rust_name: final_field_name,
                     struct_id,
                     flatten: true,
+                    is_deprecated: false,
+                    deprecation_message: None,
                 });
 
                 // We stop here, because the structs for the fragments are generated separately, to
@@ -263,10 +394,12 @@ struct ExpandedField<'a> {
     field_type_qualifiers: &'a [GraphqlTypeQualifier],
     struct_id: ResponseTypeId,
     flatten: bool,
+    is_deprecated: bool,
+    deprecation_message: Option<Cow<'a, str>>,
 }
 
 impl<'a> ExpandedField<'a> {
-    fn render(&self) -> TokenStream {
+    fn render(&self, options: &GraphQLClientCodegenOptions) -> TokenStream {
         let ident = Ident::new(&self.rust_name, Span::call_site());
         let qualified_type = decorate_type(
             &Ident::new(&self.field_type, Span::call_site()),
@@ -280,24 +413,23 @@ impl<'a> ExpandedField<'a> {
             None
         };
 
-        // TODO: deprecation
-        // let deprecation_annotation = match (
-        //     field.schema_field().is_deprecated(),
-        //     options.deprecation_strategy(),
-        // ) {
-        //     (false, _) | (true, DeprecationStrategy::Allow) => None,
-        //     (true, DeprecationStrategy::Warn) => {
-        //         let msg = field
-        //             .schema_field()
-        //             .deprecation_message()
-        //             .unwrap_or("This field is deprecated.");
-
-        //         Some(quote!(#[deprecated(note = #msg)]))
-        //     }
-        //     (true, DeprecationStrategy::Deny) => continue,
-        // };
+        // Fields denied by the deprecation strategy are filtered out before they ever become an
+        // `ExpandedField` (see `calculate_selection`), so only `Allow` and `Warn` remain here.
+        let deprecation_annotation = match (self.is_deprecated, options.deprecation_strategy()) {
+            (false, _) | (true, DeprecationStrategy::Allow) => None,
+            (true, DeprecationStrategy::Warn) => {
+                let msg = self
+                    .deprecation_message
+                    .as_deref()
+                    .unwrap_or("This field is deprecated.");
+
+                Some(quote!(#[deprecated(note = #msg)]))
+            }
+            (true, DeprecationStrategy::Deny) => None,
+        };
 
         quote! {
+            #deprecation_annotation
             #optional_flatten
             #optional_rename
             pub #ident: #qualified_type
@@ -306,20 +438,33 @@ impl<'a> ExpandedField<'a> {
 }
 
 struct ExpandedVariant<'a> {
+    /// The GraphQL concrete type name, as carried over the wire in `__typename`.
     name: Cow<'a, str>,
+    /// The Rust identifier for the variant, after the configured rename rule is applied.
+    rust_name: Cow<'a, str>,
     variant_type: Option<Cow<'a, str>>,
+    /// Whether this is the catch-all variant for concrete types with no matching selection.
+    /// Rendered as a unit variant tagged `#[serde(other)]`, the only shape serde accepts for a
+    /// fallback variant of an internally tagged enum.
+    is_other: bool,
     on: ResponseTypeId,
 }
 
 impl<'a> ExpandedVariant<'a> {
     fn render(&self) -> TokenStream {
-        let name_ident = Ident::new(&self.name, Span::call_site());
+        let name_ident = Ident::new(&self.rust_name, Span::call_site());
+
+        if self.is_other {
+            return quote!(#[serde(other)] #name_ident);
+        }
+
         let optional_type_ident = self.variant_type.as_ref().map(|variant_type| {
             let ident = Ident::new(&variant_type, Span::call_site());
             quote!((#ident))
         });
+        let optional_rename = field_rename_annotation(&self.name, &self.rust_name);
 
-        quote!(#name_ident #optional_type_ident)
+        quote!(#optional_rename #name_ident #optional_type_ident)
     }
 }
 
@@ -342,6 +487,10 @@ impl<'a> ExpandedSelection<'a> {
         self.schema
     }
 
+    pub(crate) fn options(&self) -> &'a GraphQLClientCodegenOptions {
+        self.options
+    }
+
     pub(crate) fn push_type(&mut self, tpe: ExpandedType<'a>) -> ResponseTypeId {
         let id = self.types.len();
         self.types.push(tpe);
@@ -370,8 +519,8 @@ impl<'a> ExpandedSelection<'a> {
         let name = field
             .alias()
             .unwrap_or_else(|| field.schema_field(self.schema).name());
-        let snake_case_name = name.to_snake_case();
-        let final_name = keyword_replace(snake_case_name);
+        let renamed = apply_rename_rule(name, self.options.rename_rule());
+        let final_name = keyword_replace(renamed);
 
         (name, final_name)
     }
@@ -392,7 +541,7 @@ impl<'a> ExpandedSelection<'a> {
                 .fields
                 .iter()
                 .filter(|field| field.struct_id == type_id)
-                .map(|field| field.render());
+                .map(|field| field.render(self.options));
 
             let on_variants: Vec<TokenStream> = self
                 .variants
@@ -408,6 +557,7 @@ impl<'a> ExpandedSelection<'a> {
 
                 let on_enum = quote!(
                     #response_derives
+                    #[serde(tag = "__typename")]
                     pub enum #enum_name {
                         #(#on_variants),*
                     }
@@ -434,3 +584,182 @@ impl<'a> ExpandedSelection<'a> {
         quote!(#(#items)*)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_module_token_stream, GraphQLClientCodegenOptions};
+    use std::fs;
+
+    const SCHEMA: &str = r#"
+        schema { query: Query }
+
+        type Query {
+            hero: Hero!
+        }
+
+        type Hero {
+            id: ID!
+            name: String!
+        }
+    "#;
+
+    const PET_SCHEMA: &str = r#"
+        schema { query: Query }
+
+        type Query {
+            pet: Pet!
+        }
+
+        interface Pet {
+            name: String!
+        }
+
+        type Dog implements Pet {
+            name: String!
+            breed: String!
+        }
+
+        type Cat implements Pet {
+            name: String!
+        }
+    "#;
+
+    /// Generates the response data code for `query` against `schema`.
+    fn generate_with_schema(schema: &str, query: &str) -> TokenStream {
+        // Tests run in parallel within one process, so each call needs its own schema file.
+        static NEXT_SCHEMA_FILE: std::sync::atomic::AtomicUsize =
+            std::sync::atomic::AtomicUsize::new(0);
+        let file_index = NEXT_SCHEMA_FILE.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let schema_path = std::env::temp_dir().join(format!(
+            "selection-rs-test-schema-{}-{}.graphql",
+            std::process::id(),
+            file_index
+        ));
+        fs::write(&schema_path, schema).unwrap();
+
+        let options = GraphQLClientCodegenOptions::new(crate::CodegenMode::Cli);
+        let tokens =
+            generate_module_token_stream(query.to_string(), &schema_path, options).unwrap();
+
+        fs::remove_file(&schema_path).ok();
+
+        tokens
+    }
+
+    /// Generates the response data code for `query` against `SCHEMA`.
+    fn generate(query: &str) -> TokenStream {
+        generate_with_schema(SCHEMA, query)
+    }
+
+    #[test]
+    fn single_fragment_spread_skips_wrapper_struct() {
+        let query = r#"
+            query HeroQuery {
+                hero {
+                    ...HeroFields
+                }
+            }
+
+            fragment HeroFields on Hero {
+                id
+                name
+            }
+        "#;
+
+        let tokens = generate(query);
+        let generated = tokens.to_string();
+
+        // The `hero` field's entire sub-selection is the `HeroFields` spread, so no
+        // `HeroQueryHero` wrapper struct should be generated...
+        assert!(!generated.contains("struct HeroQueryHero"));
+        // ...the field should be typed directly as the fragment's own struct instead...
+        assert!(generated.contains("pub hero : HeroFields"));
+
+        // The flattened-equivalent shape has to still be valid, deserializable Rust: parse the
+        // generated tokens to confirm they form a well-formed module (the `HeroFields` struct
+        // derives `Deserialize` like any other response struct, so it round-trips the same JSON
+        // shape the wrapper struct would have).
+        syn::parse2::<syn::File>(tokens).expect("generated code must be syntactically valid");
+    }
+
+    #[test]
+    fn interface_inline_fragment_dispatches_to_its_own_variant() {
+        let query = r#"
+            query PetQuery {
+                pet {
+                    __typename
+                    name
+                    ... on Dog {
+                        breed
+                    }
+                }
+            }
+        "#;
+
+        let tokens = generate_with_schema(PET_SCHEMA, query);
+        let generated = tokens.to_string();
+
+        // The `Dog` variant gets its own struct for the fields selected under the inline
+        // fragment...
+        assert!(generated.contains("struct PetQueryPetOnDog"));
+        assert!(generated.contains("breed"));
+        // ...while `Cat`, which has no selection of its own, is a unit variant with no payload.
+        assert!(generated.contains("Cat"));
+        // The enum is exhaustive over the schema's variants plus the catch-all fallback, which
+        // can only be expressed as a unit variant tagged `#[serde(other)]`.
+        assert!(generated.contains("Other"));
+        assert!(generated.contains("serde (other)") || generated.contains("serde(other)"));
+
+        syn::parse2::<syn::File>(tokens).expect("generated code must be syntactically valid");
+    }
+
+    #[test]
+    fn interface_type_refining_fragment_spread_is_flattened_into_its_variant() {
+        let query = r#"
+            query PetQuery {
+                pet {
+                    __typename
+                    name
+                    ...DogFields
+                }
+            }
+
+            fragment DogFields on Dog {
+                breed
+            }
+        "#;
+
+        let tokens = generate_with_schema(PET_SCHEMA, query);
+        let generated = tokens.to_string();
+
+        // The `DogFields` fragment only applies to the `Dog` variant, so it is flattened into
+        // that variant's own struct rather than pulled up onto every variant.
+        assert!(generated.contains("DogFields"));
+        assert!(generated.contains("serde (flatten)") || generated.contains("serde(flatten)"));
+
+        syn::parse2::<syn::File>(tokens).expect("generated code must be syntactically valid");
+    }
+
+    #[test]
+    fn other_variant_catches_unrecognized_typename() {
+        // `ExpandedVariant::render` emits exactly this shape for the catch-all variant: a unit
+        // variant tagged `#[serde(other)]` on an internally-tagged enum. Exercise that shape
+        // directly, since the generated code itself can't be compiled and deserialized here.
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        #[serde(tag = "__typename")]
+        enum PetQueryPetOn {
+            Dog,
+            Cat,
+            #[serde(other)]
+            Other,
+        }
+
+        let dog: PetQueryPetOn = serde_json::from_str(r#"{"__typename": "Dog"}"#).unwrap();
+        assert_eq!(dog, PetQueryPetOn::Dog);
+
+        let unrecognized: PetQueryPetOn =
+            serde_json::from_str(r#"{"__typename": "Snake"}"#).unwrap();
+        assert_eq!(unrecognized, PetQueryPetOn::Other);
+    }
+}
@@ -0,0 +1,73 @@
+mod codegen;
+mod deprecation;
+mod field_type;
+mod rename;
+mod resolution;
+mod schema;
+mod shared;
+
+use proc_macro2::TokenStream;
+use std::path::Path;
+
+pub use rename::RenameRule;
+
+/// Which context the generated code is being produced for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodegenMode {
+    Cli,
+    Derive,
+}
+
+/// Parameters and options for the generated code.
+pub struct GraphQLClientCodegenOptions {
+    mode: CodegenMode,
+    deprecation_strategy: Option<deprecation::DeprecationStrategy>,
+    rename_rule: RenameRule,
+}
+
+impl GraphQLClientCodegenOptions {
+    pub fn new(mode: CodegenMode) -> Self {
+        Self {
+            mode,
+            deprecation_strategy: None,
+            rename_rule: RenameRule::default(),
+        }
+    }
+
+    pub fn mode(&self) -> CodegenMode {
+        self.mode
+    }
+
+    pub fn deprecation_strategy(&self) -> deprecation::DeprecationStrategy {
+        self.deprecation_strategy
+            .unwrap_or(deprecation::DeprecationStrategy::Allow)
+    }
+
+    pub fn set_deprecation_strategy(&mut self, strategy: deprecation::DeprecationStrategy) {
+        self.deprecation_strategy = Some(strategy);
+    }
+
+    /// The rename rule applied to GraphQL field names and to union/interface variant type
+    /// names when generating Rust identifiers. Defaults to [`RenameRule::SnakeCase`], the
+    /// casing codegen used for field names before this option existed; variant names, which
+    /// used to keep the schema type name unchanged, are converted by the same rule.
+    pub fn rename_rule(&self) -> RenameRule {
+        self.rename_rule
+    }
+
+    pub fn set_rename_rule(&mut self, rule: RenameRule) {
+        self.rename_rule = rule;
+    }
+}
+
+/// Generates the Rust module for `query` against `schema_path`, according to `options`.
+pub fn generate_module_token_stream(
+    query_string: String,
+    schema_path: &Path,
+    options: GraphQLClientCodegenOptions,
+) -> Result<TokenStream, Box<dyn std::error::Error>> {
+    let schema = schema::Schema::parse(schema_path)?;
+    let query = resolution::ResolvedQuery::parse(&query_string, &schema)?;
+
+    Ok(codegen::response_for_query(schema, query, &options))
+}
@@ -0,0 +1,26 @@
+//! Rules for turning GraphQL names into Rust identifiers.
+
+/// Controls how GraphQL field names and union/interface variant type names are converted
+/// into Rust identifiers in generated code.
+///
+/// Defaults to [`RenameRule::SnakeCase`], which matches the casing codegen has always
+/// applied to field names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenameRule {
+    /// Convert to `lowerCamelCase`.
+    CamelCase,
+    /// Convert to `snake_case`.
+    SnakeCase,
+    /// Convert to `SCREAMING_SNAKE_CASE`.
+    ScreamingSnakeCase,
+    /// Convert to `PascalCase`.
+    PascalCase,
+    /// Leave the name untouched.
+    None,
+}
+
+impl Default for RenameRule {
+    fn default() -> Self {
+        RenameRule::SnakeCase
+    }
+}